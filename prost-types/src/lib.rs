@@ -10,8 +10,11 @@
 //! [1]: https://developers.google.com/protocol-buffers/docs/reference/google.protobuf
 
 use std::convert::TryFrom;
+use std::fmt;
 use std::i32;
 use std::i64;
+use std::ops::{Add, Neg, Sub};
+use std::str::FromStr;
 use std::time;
 
 include!("protobuf.rs");
@@ -24,30 +27,187 @@ pub mod compiler {
 // are defined in both directions.
 
 const NANOS_PER_SECOND: i32 = 1_000_000_000;
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// An error indicating that a `Timestamp` could not be parsed from its RFC 3339
+/// representation, or that `Timestamp::try_new` or `Duration::try_new` was given a value
+/// outside of the representable range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimestampError {
+    /// The input string did not match the RFC 3339 grammar.
+    ParseFailure(String),
+    /// The seconds component falls outside of the range representable by the type being
+    /// constructed.
+    OutOfRangeSeconds(i64),
+}
+
+impl fmt::Display for TimestampError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimestampError::ParseFailure(msg) => write!(f, "failed to parse timestamp: {}", msg),
+            TimestampError::OutOfRangeSeconds(seconds) => {
+                write!(f, "seconds out of range: {}", seconds)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TimestampError {}
+
+/// Converts a civil date (year, month, day) to the number of days since the Unix epoch,
+/// using Howard Hinnant's `days_from_civil` algorithm, so that we don't need a date library
+/// just to format and parse RFC 3339 timestamps.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400; // [0, 399]
+    let mp = (month + 9) % 12; // [0, 11]
+    let doy = (153 * mp + 2) / 5 + day - 1; // [0, 365]
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy; // [0, 146096]
+    era * 146_097 + doe - 719_468
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+/// Returns the number of days in the given month of the given year, or `0` if `month` is not
+/// in `1..=12`.
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 => {
+            if is_leap_year(year) {
+                29
+            } else {
+                28
+            }
+        }
+        _ => 0,
+    }
+}
+
+/// The inverse of [`days_from_civil`]: converts a day count since the Unix epoch back into a
+/// civil (year, month, day) triple.
+fn civil_from_days(z: i64) -> (i64, i64, i64) {
+    let z = z + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = z - era * 146_097; // [0, 146096]
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365; // [0, 399]
+    let y = yoe + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100); // [0, 365]
+    let mp = (5 * doy + 2) / 153; // [0, 11]
+    let day = doy - (153 * mp + 2) / 5 + 1; // [1, 31]
+    let month = if mp < 10 { mp + 3 } else { mp - 9 }; // [1, 12]
+    let year = if month <= 2 { y + 1 } else { y };
+    (year, month, day)
+}
 
 impl Duration {
+    /// A `Duration` of zero length.
+    pub const ZERO: Duration = Duration {
+        seconds: 0,
+        nanos: 0,
+    };
+
     /// Normalizes the duration to a canonical format.
     ///
     /// Based on [`google::protobuf::util::CreateNormalized`][1].
     /// [1]: https://github.com/google/protobuf/blob/v3.3.2/src/google/protobuf/util/time_util.cc#L79-L100
     fn normalize(&mut self) {
-        // Make sure nanos is in the range.
+        // Make sure nanos is in the range. Carrying into seconds uses saturating arithmetic so
+        // that a pathological `nanos` can't overflow `seconds`; the result is still guaranteed
+        // to be out of the valid `Timestamp`/`Duration` range, so callers that check bounds
+        // (e.g. `try_new`) will reject it instead of silently wrapping.
         if self.nanos <= -NANOS_PER_SECOND || self.nanos >= NANOS_PER_SECOND {
-            self.seconds += (self.nanos / NANOS_PER_SECOND) as i64;
+            self.seconds = self
+                .seconds
+                .saturating_add((self.nanos / NANOS_PER_SECOND) as i64);
             self.nanos %= NANOS_PER_SECOND;
         }
 
         // nanos should have the same sign as seconds.
         if self.seconds < 0 && self.nanos > 0 {
-            self.seconds += 1;
+            self.seconds = self.seconds.saturating_add(1);
             self.nanos -= NANOS_PER_SECOND;
         } else if self.seconds > 0 && self.nanos < 0 {
-            self.seconds -= 1;
+            self.seconds = self.seconds.saturating_sub(1);
             self.nanos += NANOS_PER_SECOND;
         }
-        // TODO: should this be checked?
-        // debug_assert!(self.seconds >= -315_576_000_000 && self.seconds <= 315_576_000_000,
-        //               "invalid duration: {:?}", self);
+    }
+
+    /// Constructs a `Duration` from seconds and nanos, validating that the result falls within
+    /// the range of durations representable by `google.protobuf.Duration`
+    /// (`±315,576,000,000` seconds).
+    pub fn try_new(seconds: i64, nanos: i32) -> Result<Duration, TimestampError> {
+        let mut duration = Duration { seconds, nanos };
+        duration.normalize();
+        if duration.seconds < -315_576_000_000 || duration.seconds > 315_576_000_000 {
+            return Err(TimestampError::OutOfRangeSeconds(duration.seconds));
+        }
+        Ok(duration)
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+
+    fn add(self, other: Duration) -> Duration {
+        let mut duration = Duration {
+            seconds: self.seconds.saturating_add(other.seconds),
+            nanos: self.nanos.saturating_add(other.nanos),
+        };
+        duration.normalize();
+        duration
+    }
+}
+
+impl Sub for Duration {
+    type Output = Duration;
+
+    fn sub(self, other: Duration) -> Duration {
+        let mut duration = Duration {
+            seconds: self.seconds.saturating_sub(other.seconds),
+            nanos: self.nanos.saturating_sub(other.nanos),
+        };
+        duration.normalize();
+        duration
+    }
+}
+
+impl Neg for Duration {
+    type Output = Duration;
+
+    fn neg(self) -> Duration {
+        let mut duration = Duration {
+            seconds: self.seconds.saturating_neg(),
+            nanos: self.nanos.saturating_neg(),
+        };
+        duration.normalize();
+        duration
+    }
+}
+
+/// Converts a `time::Duration` to a `Duration`.
+#[cfg(feature = "time-conversions")]
+impl From<::time::Duration> for Duration {
+    fn from(duration: ::time::Duration) -> Duration {
+        let mut duration = Duration {
+            seconds: duration.whole_seconds(),
+            nanos: duration.subsec_nanoseconds(),
+        };
+        duration.normalize();
+        duration
+    }
+}
+
+/// Converts a `Duration` to a `time::Duration`.
+#[cfg(feature = "time-conversions")]
+impl From<Duration> for ::time::Duration {
+    fn from(mut duration: Duration) -> ::time::Duration {
+        duration.normalize();
+        ::time::Duration::new(duration.seconds, duration.nanos)
     }
 }
 
@@ -94,26 +254,89 @@ impl TryFrom<Duration> for time::Duration {
 }
 
 impl Timestamp {
+    /// The `Timestamp` corresponding to the Unix epoch, 1970-01-01T00:00:00Z.
+    pub const UNIX_EPOCH: Timestamp = Timestamp {
+        seconds: 0,
+        nanos: 0,
+    };
+
+    /// Returns a `Timestamp` for the current time.
+    #[cfg(feature = "std")]
+    pub fn now() -> Timestamp {
+        Timestamp::from(time::SystemTime::now())
+    }
+
     /// Normalizes the timestamp to a canonical format.
     ///
     /// Based on [`google::protobuf::util::CreateNormalized`][1].
     /// [1]: https://github.com/google/protobuf/blob/v3.3.2/src/google/protobuf/util/time_util.cc#L59-L77
     fn normalize(&mut self) {
-        // Make sure nanos is in the range.
+        // Make sure nanos is in the range. Carrying into seconds uses saturating arithmetic so
+        // that a pathological `nanos` can't overflow `seconds`; see the comment on
+        // `Duration::normalize`.
         if self.nanos <= -NANOS_PER_SECOND || self.nanos >= NANOS_PER_SECOND {
-            self.seconds += (self.nanos / NANOS_PER_SECOND) as i64;
+            self.seconds = self
+                .seconds
+                .saturating_add((self.nanos / NANOS_PER_SECOND) as i64);
             self.nanos %= NANOS_PER_SECOND;
         }
 
         // For Timestamp nanos should be in the range [0, 999999999].
         if self.nanos < 0 {
-            self.seconds -= 1;
+            self.seconds = self.seconds.saturating_sub(1);
             self.nanos += NANOS_PER_SECOND;
         }
+    }
+
+    /// Constructs a `Timestamp` from seconds and nanos, validating that the result falls within
+    /// the range of dates representable by `google.protobuf.Timestamp`
+    /// (`0001-01-01T00:00:00Z` to `9999-12-31T23:59:59.999999999Z`).
+    pub fn try_new(seconds: i64, nanos: i32) -> Result<Timestamp, TimestampError> {
+        let mut timestamp = Timestamp { seconds, nanos };
+        timestamp.normalize();
+        if timestamp.seconds < -62_135_596_800 || timestamp.seconds > 253_402_300_799 {
+            return Err(TimestampError::OutOfRangeSeconds(timestamp.seconds));
+        }
+        Ok(timestamp)
+    }
+}
+
+impl Add<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, rhs: Duration) -> Timestamp {
+        let mut timestamp = Timestamp {
+            seconds: self.seconds.saturating_add(rhs.seconds),
+            nanos: self.nanos.saturating_add(rhs.nanos),
+        };
+        timestamp.normalize();
+        timestamp
+    }
+}
 
-        // TODO: should this be checked?
-        // debug_assert!(self.seconds >= -62_135_596_800 && self.seconds <= 253_402_300_799,
-        //               "invalid timestamp: {:?}", self);
+impl Sub<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, rhs: Duration) -> Timestamp {
+        let mut timestamp = Timestamp {
+            seconds: self.seconds.saturating_sub(rhs.seconds),
+            nanos: self.nanos.saturating_sub(rhs.nanos),
+        };
+        timestamp.normalize();
+        timestamp
+    }
+}
+
+impl Sub<Timestamp> for Timestamp {
+    type Output = Duration;
+
+    fn sub(self, rhs: Timestamp) -> Duration {
+        let mut duration = Duration {
+            seconds: self.seconds.saturating_sub(rhs.seconds),
+            nanos: self.nanos.saturating_sub(rhs.nanos),
+        };
+        duration.normalize();
+        duration
     }
 }
 
@@ -137,6 +360,31 @@ impl Into<chrono::DateTime<chrono::Utc>> for Timestamp {
     }
 }
 
+/// Converts a `time::OffsetDateTime` to a `Timestamp`.
+#[cfg(feature = "time-conversions")]
+impl From<::time::OffsetDateTime> for Timestamp {
+    fn from(dt: ::time::OffsetDateTime) -> Self {
+        let mut timestamp = Timestamp {
+            seconds: dt.unix_timestamp(),
+            nanos: dt.nanosecond() as i32,
+        };
+        timestamp.normalize();
+        timestamp
+    }
+}
+
+/// Converts a `Timestamp` to a `time::OffsetDateTime`.
+#[cfg(feature = "time-conversions")]
+impl TryFrom<Timestamp> for ::time::OffsetDateTime {
+    type Error = ::time::error::ComponentRange;
+
+    fn try_from(mut timestamp: Timestamp) -> Result<::time::OffsetDateTime, Self::Error> {
+        timestamp.normalize();
+        let nanos = timestamp.seconds as i128 * NANOS_PER_SECOND as i128 + timestamp.nanos as i128;
+        ::time::OffsetDateTime::from_unix_timestamp_nanos(nanos)
+    }
+}
+
 /// Converts a `std::time::SystemTime` to a `Timestamp`.
 impl From<time::SystemTime> for Timestamp {
     fn from(time: time::SystemTime) -> Timestamp {
@@ -172,7 +420,136 @@ impl TryFrom<Timestamp> for time::SystemTime {
     }
 }
 
+/// Formats a timestamp according to RFC 3339, e.g. `2014-07-08T09:10:11.000000012Z`, the
+/// representation used by protobuf's JSON mapping for `google.protobuf.Timestamp`.
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut ts = *self;
+        ts.normalize();
+
+        let date_days = ts.seconds.div_euclid(SECONDS_PER_DAY);
+        let secs_of_day = ts.seconds.rem_euclid(SECONDS_PER_DAY);
+        let (year, month, day) = civil_from_days(date_days);
+
+        let hour = secs_of_day / 3600;
+        let minute = (secs_of_day % 3600) / 60;
+        let second = secs_of_day % 60;
+
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            year, month, day, hour, minute, second
+        )?;
+
+        if ts.nanos % 1_000_000_000 != 0 {
+            if ts.nanos % 1_000_000 == 0 {
+                write!(f, ".{:03}", ts.nanos / 1_000_000)?;
+            } else if ts.nanos % 1_000 == 0 {
+                write!(f, ".{:06}", ts.nanos / 1_000)?;
+            } else {
+                write!(f, ".{:09}", ts.nanos)?;
+            }
+        }
+
+        write!(f, "Z")
+    }
+}
+
+/// Parses a timestamp in RFC 3339 format, e.g. `2014-07-08T09:10:11.000000012Z`.
+impl FromStr for Timestamp {
+    type Err = TimestampError;
+
+    fn from_str(s: &str) -> Result<Timestamp, TimestampError> {
+        fn invalid(msg: impl Into<String>) -> TimestampError {
+            TimestampError::ParseFailure(msg.into())
+        }
+
+        fn digits(s: &str) -> Result<i64, TimestampError> {
+            if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid(format!("expected digits, found {:?}", s)));
+            }
+            s.parse::<i64>()
+                .map_err(|_| invalid(format!("number out of range: {:?}", s)))
+        }
+
+        if !s.is_ascii() {
+            return Err(invalid("timestamp must be ASCII"));
+        }
+        if s.len() < 20 {
+            return Err(invalid("timestamp is too short"));
+        }
+        if &s[4..5] != "-"
+            || &s[7..8] != "-"
+            || &s[10..11] != "T"
+            || &s[13..14] != ":"
+            || &s[16..17] != ":"
+        {
+            return Err(invalid("timestamp is missing expected separators"));
+        }
+
+        let year = digits(&s[0..4])?;
+        let month = digits(&s[5..7])?;
+        let day = digits(&s[8..10])?;
+        let hour = digits(&s[11..13])?;
+        let minute = digits(&s[14..16])?;
+        let second = digits(&s[17..19])?;
+
+        if !(1..=12).contains(&month) {
+            return Err(invalid("month is out of range"));
+        }
+        if day < 1 || day > days_in_month(year, month) {
+            return Err(invalid("day is out of range for the given month"));
+        }
+        if hour > 23 || minute > 59 || second > 59 {
+            return Err(invalid("time is out of range"));
+        }
+
+        let mut rest = &s[19..];
+        let mut nanos = 0i32;
+        if let Some(stripped) = rest.strip_prefix('.') {
+            let frac_len = stripped
+                .find(|c: char| !c.is_ascii_digit())
+                .ok_or_else(|| invalid("timestamp is missing a UTC offset"))?;
+            let frac = &stripped[..frac_len];
+            if frac.is_empty() || frac.len() > 9 {
+                return Err(invalid("fractional seconds must have 1 to 9 digits"));
+            }
+            let mut padded = String::with_capacity(9);
+            padded.push_str(frac);
+            while padded.len() < 9 {
+                padded.push('0');
+            }
+            nanos = padded
+                .parse::<i32>()
+                .map_err(|_| invalid("invalid fractional seconds"))?;
+            rest = &stripped[frac_len..];
+        }
+
+        let offset_seconds = if rest == "Z" || rest == "z" {
+            0
+        } else if rest.len() == 6 && (rest.starts_with('+') || rest.starts_with('-')) {
+            let sign = if rest.starts_with('-') { -1 } else { 1 };
+            let offset_hours = digits(&rest[1..3])?;
+            if &rest[3..4] != ":" {
+                return Err(invalid("UTC offset is missing the expected separator"));
+            }
+            let offset_minutes = digits(&rest[4..6])?;
+            if offset_hours > 23 || offset_minutes > 59 {
+                return Err(invalid("UTC offset is out of range"));
+            }
+            sign * (offset_hours * 3600 + offset_minutes * 60)
+        } else {
+            return Err(invalid("timestamp has an invalid UTC offset"));
+        };
 
+        let days = days_from_civil(year, month, day);
+        let seconds = days * SECONDS_PER_DAY + hour * 3600 + minute * 60 + second - offset_seconds;
+
+        let mut timestamp = Timestamp { seconds, nanos };
+        timestamp.normalize();
+        Ok(timestamp)
+    }
+}
 
 mod test {
     #[test]
@@ -203,4 +580,146 @@ mod test {
         let date: DateTime<Utc> = ts.into();
         assert_eq!(date, expected_date);
     }
+
+    #[test]
+    #[cfg(feature = "time-conversions")]
+    fn test_datetime_to_wkt_timestamp_time() {
+        use super::*;
+        use ::time::OffsetDateTime;
+        use std::convert::TryFrom;
+
+        let dt = OffsetDateTime::from_unix_timestamp_nanos(1404810611_000000012).unwrap();
+        let ts: Timestamp = dt.into();
+        assert_eq!(ts, Timestamp{seconds: 1404810611, nanos: 12});
+
+        let back = OffsetDateTime::try_from(ts).unwrap();
+        assert_eq!(back, dt);
+    }
+
+    #[test]
+    fn test_try_new_range_validation() {
+        use super::*;
+
+        assert_eq!(
+            Timestamp::try_new(0, 0),
+            Ok(Timestamp{seconds: 0, nanos: 0}),
+        );
+        assert_eq!(
+            Timestamp::try_new(-62_135_596_801, 0),
+            Err(TimestampError::OutOfRangeSeconds(-62_135_596_801)),
+        );
+        assert_eq!(
+            Timestamp::try_new(253_402_300_800, 0),
+            Err(TimestampError::OutOfRangeSeconds(253_402_300_800)),
+        );
+
+        assert_eq!(
+            Duration::try_new(0, 0),
+            Ok(Duration{seconds: 0, nanos: 0}),
+        );
+        assert_eq!(
+            Duration::try_new(-315_576_000_001, 0),
+            Err(TimestampError::OutOfRangeSeconds(-315_576_000_001)),
+        );
+        assert_eq!(
+            Duration::try_new(315_576_000_001, 0),
+            Err(TimestampError::OutOfRangeSeconds(315_576_000_001)),
+        );
+
+        // A pathological `nanos` large enough to overflow `seconds` during the carry must be
+        // rejected rather than panicking or wrapping around to a bogus in-range value.
+        assert_eq!(
+            Timestamp::try_new(i64::MAX, 1_000_000_000),
+            Err(TimestampError::OutOfRangeSeconds(i64::MAX)),
+        );
+        assert_eq!(
+            Duration::try_new(i64::MIN, -1_000_000_000),
+            Err(TimestampError::OutOfRangeSeconds(i64::MIN)),
+        );
+    }
+
+    #[test]
+    fn test_constants() {
+        use super::*;
+
+        assert_eq!(Duration::ZERO, Duration{seconds: 0, nanos: 0});
+        assert_eq!(Timestamp::UNIX_EPOCH, Timestamp{seconds: 0, nanos: 0});
+        assert_eq!(Timestamp::UNIX_EPOCH.to_string(), "1970-01-01T00:00:00Z");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_timestamp_now() {
+        use super::*;
+
+        let now = Timestamp::now();
+        assert!(now.seconds > 0);
+    }
+
+    #[test]
+    fn test_timestamp_duration_arithmetic() {
+        use super::*;
+
+        let ts = Timestamp{seconds: 10, nanos: 500_000_000};
+        let d = Duration{seconds: 1, nanos: 600_000_000};
+
+        assert_eq!(ts + d, Timestamp{seconds: 12, nanos: 100_000_000});
+        assert_eq!(ts - d, Timestamp{seconds: 8, nanos: 900_000_000});
+        assert_eq!(ts - Timestamp{seconds: 5, nanos: 0}, Duration{seconds: 5, nanos: 500_000_000});
+        assert_eq!(-d, Duration{seconds: -1, nanos: -600_000_000});
+    }
+
+    #[test]
+    fn test_timestamp_duration_arithmetic_does_not_overflow() {
+        use super::*;
+
+        // These must saturate rather than panic or silently wrap.
+        let _ = -Duration{seconds: i64::MIN, nanos: 0};
+        let _ = Timestamp{seconds: i64::MAX - 1, nanos: 0} + Duration{seconds: 10, nanos: 0};
+        let _ = Timestamp{seconds: i64::MIN + 1, nanos: 0} - Duration{seconds: 10, nanos: 0};
+        let _ = Timestamp{seconds: i64::MAX, nanos: 0} - Timestamp{seconds: i64::MIN, nanos: 0};
+    }
+
+    #[test]
+    fn test_rfc3339_display() {
+        use super::*;
+
+        let ts = Timestamp{seconds: 1404810611, nanos: 12};
+        assert_eq!(ts.to_string(), "2014-07-08T09:10:11.000000012Z");
+
+        let ts_no_nanos = Timestamp{seconds: 1404810611, nanos: 0};
+        assert_eq!(ts_no_nanos.to_string(), "2014-07-08T09:10:11Z");
+
+        let ts_millis = Timestamp{seconds: 1404810611, nanos: 123_000_000};
+        assert_eq!(ts_millis.to_string(), "2014-07-08T09:10:11.123Z");
+    }
+
+    #[test]
+    fn test_rfc3339_parse() {
+        use super::*;
+
+        let ts: Timestamp = "2014-07-08T09:10:11.000000012Z".parse().unwrap();
+        assert_eq!(ts, Timestamp{seconds: 1404810611, nanos: 12});
+
+        let ts: Timestamp = "2014-07-08T15:10:11+06:00".parse().unwrap();
+        assert_eq!(ts, Timestamp{seconds: 1404810611, nanos: 0});
+
+        assert!("not a timestamp".parse::<Timestamp>().is_err());
+        assert!("123é-06-08T09:10:11.123Z".parse::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn test_rfc3339_parse_rejects_invalid_dates() {
+        use super::*;
+
+        assert!("2014-02-30T00:00:00Z".parse::<Timestamp>().is_err());
+        assert!("2015-02-29T00:00:00Z".parse::<Timestamp>().is_err());
+        assert!("2014-04-31T00:00:00Z".parse::<Timestamp>().is_err());
+        assert!("2014-07-08T00:00:00+25:00".parse::<Timestamp>().is_err());
+        assert!("2014-07-08T00:00:00+00:61".parse::<Timestamp>().is_err());
+
+        // 2016 is a leap year, so February 29th is valid.
+        let ts: Timestamp = "2016-02-29T00:00:00Z".parse().unwrap();
+        assert_eq!(ts.to_string(), "2016-02-29T00:00:00Z");
+    }
 }